@@ -33,25 +33,214 @@
 //! assert_eq!(deserialized, data);
 //! ```
 //!
+//! ## Public-key (hybrid) mode
+//!
+//! [`setup_recipient`] and [`setup_identity`] switch `e`/`d` to per-call X25519
+//! ECDH instead of a pre-shared master key, so two parties can exchange
+//! encrypted structs without ever sharing a symmetric secret.
+//!
+//! ## Inner serialization format
+//!
+//! [`set_format`] picks the codec used to serialize a struct before sealing
+//! it. `Format::Json` is the default; `Format::Bincode`/`Format::Cbor` trade
+//! human-readability for a smaller plaintext (and thus smaller ciphertext),
+//! which matters most for fields holding large binary blobs.
+//!
 
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::Display;
 use std::sync::Mutex;
+use std::thread_local;
 
 use base64::engine::general_purpose;
 use base64::Engine;
 use once_cell::sync::Lazy;
-use ring::aead::{
-    Aad, BoundKey, Nonce, NonceSequence, OpeningKey, SealingKey, UnboundKey, AES_256_GCM, NONCE_LEN,
-};
-use ring::digest::{self, digest};
+use ring::aead::{self, Aad, BoundKey, Nonce, NonceSequence, OpeningKey, SealingKey, UnboundKey, NONCE_LEN};
 use ring::error::{self, Unspecified};
+use ring::hkdf;
 use ring::rand::{SecureRandom, SystemRandom};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde::{Deserializer, Serializer};
+use x25519_dalek::{PublicKey as X25519PublicKey, StaticSecret as X25519StaticSecret};
+use zeroize::{Zeroize, Zeroizing};
+
+const KEY_ID_LEN: usize = 4;
+const X25519_PUBLIC_KEY_LEN: usize = 32;
+
+/// Identifies a serde-crypt envelope so `d` can reject garbage input with a
+/// clear error instead of misparsing it as a valid, truncated one.
+const MAGIC: u8 = 0xC7;
+/// Version of the header layout below (magic, version, algorithm id, flags,
+/// format id). Bump this, not [`MAGIC`], when the header itself grows or
+/// changes shape.
+const FORMAT_VERSION: u8 = 2;
+const HEADER_LEN: usize = 5;
+
+/// Set when the body holds an ephemeral X25519 public key rather than a keyring
+/// key id, i.e. the envelope was produced in [`setup_recipient`] mode.
+const FLAG_HYBRID: u8 = 0b0000_0001;
+
+/// Whether `e`/`d` use the symmetric keyring or public-key hybrid encryption.
+///
+/// Hybrid encryption doesn't fit the keyring: there is no pre-shared key id to
+/// look up, only a one-off ECDH per call. It is kept as a separate mode rather
+/// than folded into the keyring, and marks its envelopes with [`FLAG_HYBRID`].
+#[derive(Clone)]
+enum Mode {
+    Symmetric,
+    /// A recipient's public key: not secret, so plain `Copy` bytes are fine.
+    Recipient([u8; X25519_PUBLIC_KEY_LEN]),
+    /// The one long-lived private key in the crate outside the keyring, so it
+    /// gets the same [`Zeroizing`] treatment as [`KeyEntry::key`].
+    Identity(Zeroizing<[u8; X25519_PUBLIC_KEY_LEN]>),
+}
+
+static MODE: Lazy<Mutex<Mode>> = Lazy::new(|| Mutex::new(Mode::Symmetric));
+
+struct KeyEntry {
+    /// Zeroized on drop, so replacing or disabling a key doesn't leave a copy
+    /// of it lingering in freed memory.
+    key: Zeroizing<Vec<u8>>,
+    enabled: bool,
+}
 
-static MASTER_KEY: Lazy<Mutex<Vec<u8>>> = Lazy::new(|| Mutex::new(vec![]));
+/// A keyring holding every master key that is still allowed to decrypt, plus the
+/// single primary key that new calls to [`e`] encrypt under.
+///
+/// Keeping disabled and superseded keys around (instead of a single global key)
+/// lets [`rotate`] roll the primary key without having to re-encrypt every
+/// previously serialized record.
+struct KeyState {
+    keys: HashMap<u32, KeyEntry>,
+    primary_id: u32,
+    next_id: u32,
+    algorithm: Algorithm,
+}
+
+static MASTER_KEY: Lazy<Mutex<KeyState>> = Lazy::new(|| {
+    Mutex::new(KeyState {
+        keys: HashMap::new(),
+        primary_id: 0,
+        next_id: 1,
+        algorithm: Algorithm::Aes256Gcm,
+    })
+});
+
+/// The AEAD algorithm used to seal a field, selectable via [`setup_with`].
+///
+/// The chosen algorithm's id is recorded alongside the nonce in the envelope, so
+/// [`d`] always knows which algorithm to use without any out-of-band agreement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Aes128Gcm,
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl Algorithm {
+    fn ring_algorithm(self) -> &'static aead::Algorithm {
+        match self {
+            Self::Aes128Gcm => &aead::AES_128_GCM,
+            Self::Aes256Gcm => &aead::AES_256_GCM,
+            Self::ChaCha20Poly1305 => &aead::CHACHA20_POLY1305,
+        }
+    }
+
+    /// The derived key length this algorithm requires, in bytes.
+    pub fn key_len(self) -> usize {
+        self.ring_algorithm().key_len()
+    }
+
+    fn id(self) -> u8 {
+        match self {
+            Self::Aes128Gcm => 0,
+            Self::Aes256Gcm => 1,
+            Self::ChaCha20Poly1305 => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self, CryptError> {
+        match id {
+            0 => Ok(Self::Aes128Gcm),
+            1 => Ok(Self::Aes256Gcm),
+            2 => Ok(Self::ChaCha20Poly1305),
+            other => Err(CryptError::UnknownAlgorithm(other)),
+        }
+    }
+}
+
+/// The codec used to serialize a value before it is sealed, selectable via
+/// [`set_format`].
+///
+/// The chosen format's id is recorded in the envelope header, same as
+/// [`Algorithm`], so [`d`] always decodes with the codec the value was
+/// encoded with regardless of what [`set_format`] is currently set to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Bincode,
+    Cbor,
+}
+
+impl Format {
+    fn id(self) -> u8 {
+        match self {
+            Self::Json => 0,
+            Self::Bincode => 1,
+            Self::Cbor => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self, CryptError> {
+        match id {
+            0 => Ok(Self::Json),
+            1 => Ok(Self::Bincode),
+            2 => Ok(Self::Cbor),
+            other => Err(CryptError::UnknownFormat(other)),
+        }
+    }
+
+    fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(match self {
+            Self::Json => serde_json::to_vec(value)?,
+            Self::Bincode => bincode::serialize(value)?,
+            Self::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::into_writer(value, &mut buf)?;
+                buf
+            }
+        })
+    }
+
+    fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, Box<dyn Error>> {
+        Ok(match self {
+            Self::Json => serde_json::from_slice(bytes)?,
+            Self::Bincode => bincode::deserialize(bytes)?,
+            Self::Cbor => ciborium::from_reader(bytes)?,
+        })
+    }
+}
+
+static FORMAT: Lazy<Mutex<Format>> = Lazy::new(|| Mutex::new(Format::Json));
+
+/// Sets the codec used to serialize a value before sealing it, for every
+/// subsequent call to [`e`]. Does not affect calls already in flight or
+/// previously produced envelopes, which always decode with the format
+/// recorded in their own header.
+pub fn set_format(format: Format) {
+    *FORMAT.lock().unwrap() = format;
+}
+
+struct OkmLength(usize);
+
+impl hkdf::KeyType for OkmLength {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
 
 #[allow(dead_code)]
 pub fn serialize<S: Serializer, T: Serialize>(v: T, s: S) -> Result<S::Ok, S::Error> {
@@ -65,55 +254,365 @@ pub fn deserialize<'de, D: Deserializer<'de>, T: DeserializeOwned>(de: D) -> Res
     d(base64).map_err(serde::de::Error::custom)
 }
 
+thread_local! {
+    static CONTEXT: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Sets the associated data bound into fields sealed with `#[serde(with =
+/// "serde_crypt::aad")]` on this thread, replacing whatever was set before.
+///
+/// The AAD is authenticated but **not** encrypted. It is not written into the
+/// envelope either, so the reading side must set the same context itself; a
+/// ciphertext only opens under the exact context it was sealed with. Typical usage is binding a
+/// field to a plaintext record id or tenant id, so a ciphertext cut-and-pasted
+/// from one record into another fails to decrypt instead of silently opening.
+pub fn set_context(context: Vec<u8>) {
+    CONTEXT.with(|c| *c.borrow_mut() = context);
+}
+
+/// A `#[serde(with = "serde_crypt::aad")]` variant of the root [`serialize`]/
+/// [`deserialize`] that binds the thread's current [`set_context`] value in as
+/// associated data when sealing and opening the field.
+pub mod aad {
+    use serde::de::DeserializeOwned;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::CONTEXT;
+
+    #[allow(dead_code)]
+    pub fn serialize<S: Serializer, T: Serialize>(v: T, s: S) -> Result<S::Ok, S::Error> {
+        let context = CONTEXT.with(|c| c.borrow().clone());
+        let base64 = super::e_impl(v, &context).map_err(serde::ser::Error::custom)?;
+        String::serialize(&base64, s)
+    }
+
+    #[allow(dead_code)]
+    pub fn deserialize<'de, D: Deserializer<'de>, T: DeserializeOwned>(de: D) -> Result<T, D::Error> {
+        let base64 = String::deserialize(de)?;
+        let context = CONTEXT.with(|c| c.borrow().clone());
+        super::d_impl(base64, &context).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Installs the master key as the sole, primary key, sealing with AES-256-GCM.
+///
+/// Use [`setup_with`] to pick a different algorithm, e.g. on hardware without AES
+/// acceleration.
 pub fn setup(master_key: Vec<u8>) {
-    *MASTER_KEY.lock().unwrap() = master_key;
+    setup_with(master_key, Algorithm::Aes256Gcm);
+}
+
+/// Installs the master key as the sole, primary key and selects the AEAD
+/// algorithm used to seal fields.
+pub fn setup_with(master_key: Vec<u8>, algorithm: Algorithm) {
+    let mut state = MASTER_KEY.lock().unwrap();
+    state.keys.clear();
+    state.keys.insert(
+        0,
+        KeyEntry {
+            key: Zeroizing::new(master_key),
+            enabled: true,
+        },
+    );
+    state.primary_id = 0;
+    state.next_id = 1;
+    state.algorithm = algorithm;
+    *MODE.lock().unwrap() = Mode::Symmetric;
+}
+
+/// Installs `new_key` as the new primary key, under which all subsequent calls
+/// to [`e`] encrypt. Every key installed by a previous [`setup`]/[`setup_with`]
+/// or [`rotate`] call is kept around so that records encrypted under it remain
+/// decryptable, unless later hard-disabled via [`disable_key`].
+///
+/// Returns the id assigned to the new primary key.
+pub fn rotate(new_key: Vec<u8>) -> u32 {
+    let mut state = MASTER_KEY.lock().unwrap();
+    let id = state.next_id;
+    state.next_id += 1;
+    state.keys.insert(
+        id,
+        KeyEntry {
+            key: Zeroizing::new(new_key),
+            enabled: true,
+        },
+    );
+    state.primary_id = id;
+    id
+}
+
+/// Hard-stops decryption of data encrypted under key `id`. Has no effect on the
+/// ability to encrypt new data, which is always sealed under the primary key.
+///
+/// Rejects disabling the current primary key: doing so would let [`e`] keep
+/// "successfully" sealing new data under a key that [`d`] can no longer open,
+/// silently and permanently losing it. [`rotate`] to a new primary key first.
+pub fn disable_key(id: u32) -> Result<(), CryptError> {
+    let mut state = MASTER_KEY.lock().unwrap();
+    if id == state.primary_id {
+        return Err(CryptError::CannotDisablePrimaryKey(id));
+    }
+    if let Some(entry) = state.keys.get_mut(&id) {
+        entry.enabled = false;
+    }
+    Ok(())
+}
+
+/// Drops every key in the keyring, resets the primary/next id counters, and
+/// clears any hybrid-mode identity key, zeroizing the key material in the
+/// process.
+///
+/// Intended for long-running processes (a WASM module, a server handling
+/// multiple tenants in sequence) that want secrets gone from memory
+/// deterministically between sessions, rather than waiting on the allocator to
+/// reuse and overwrite the freed memory. Call [`setup`]/[`setup_with`] or
+/// [`setup_recipient`]/[`setup_identity`] again to configure the next session.
+pub fn teardown() {
+    let mut state = MASTER_KEY.lock().unwrap();
+    state.keys.clear();
+    state.primary_id = 0;
+    state.next_id = 1;
+    *MODE.lock().unwrap() = Mode::Symmetric;
+}
+
+/// Switches `e` into public-key hybrid mode, sealing every field to `public_key`.
+///
+/// Each call to [`e`] generates a fresh ephemeral X25519 keypair, performs ECDH
+/// against `public_key`, and derives the AEAD key from the shared secret, so two
+/// parties can exchange encrypted structs without ever sharing a symmetric
+/// secret. Only the holder of the matching private key, set up via
+/// [`setup_identity`], can decrypt the result.
+pub fn setup_recipient(public_key: [u8; X25519_PUBLIC_KEY_LEN]) {
+    *MODE.lock().unwrap() = Mode::Recipient(public_key);
+}
+
+/// Switches `d` into public-key hybrid mode, opening fields sealed to `private_key`'s
+/// public counterpart via [`setup_recipient`].
+pub fn setup_identity(private_key: [u8; X25519_PUBLIC_KEY_LEN]) {
+    *MODE.lock().unwrap() = Mode::Identity(Zeroizing::new(private_key));
 }
 
 pub fn e<T: Serialize>(source: T) -> Result<String, Box<dyn Error>> {
-    let nonce = generate_random_nonce();
-    let serialized = serde_json::to_string(&source).map(|t| t.as_bytes().to_vec())?;
-    let mut encrypted = encrypt(serialized, nonce)?;
-    let mut nonce_encrypted = nonce.to_vec();
-    nonce_encrypted.append(&mut encrypted);
-    Ok(general_purpose::URL_SAFE_NO_PAD.encode(nonce_encrypted))
+    e_impl(source, &[])
 }
 
 pub fn d<T: DeserializeOwned>(source: String) -> Result<T, Box<dyn Error>> {
+    d_impl(source, &[])
+}
+
+fn e_impl<T: Serialize>(source: T, aad: &[u8]) -> Result<String, Box<dyn Error>> {
+    let format = *FORMAT.lock().unwrap();
+    let serialized = format.encode(&source)?;
+    let mode = MODE.lock().unwrap().clone();
+    let (algorithm, flags, mut body) = match mode {
+        Mode::Recipient(recipient_public_key) => {
+            let header = [MAGIC, FORMAT_VERSION, Algorithm::Aes256Gcm.id(), FLAG_HYBRID, format.id()];
+            let body = e_hybrid(serialized, recipient_public_key, &header, aad)?;
+            (Algorithm::Aes256Gcm, FLAG_HYBRID, body)
+        }
+        Mode::Identity(_) => return Err(Box::new(CryptError::MissingRecipientKey)),
+        Mode::Symmetric => {
+            let (algorithm, body) = e_symmetric(serialized, format, aad)?;
+            (algorithm, 0, body)
+        }
+    };
+
+    let mut envelope = vec![MAGIC, FORMAT_VERSION, algorithm.id(), flags, format.id()];
+    envelope.append(&mut body);
+    Ok(general_purpose::URL_SAFE_NO_PAD.encode(envelope))
+}
+
+fn d_impl<T: DeserializeOwned>(source: String, aad: &[u8]) -> Result<T, Box<dyn Error>> {
     let decoded = general_purpose::URL_SAFE_NO_PAD.decode(source.as_bytes())?;
-    let nonce = decoded[..NONCE_LEN].try_into().unwrap();
-    let data = decoded[NONCE_LEN..].to_vec();
-    let decrypted = decrypt(data, nonce)?;
-    let decrypted = std::str::from_utf8(&decrypted)?;
-    Ok(serde_json::from_str(decrypted)?)
+    if decoded.len() < HEADER_LEN {
+        return Err(Box::new(CryptError::Truncated));
+    }
+    if decoded[0] != MAGIC {
+        return Err(Box::new(CryptError::InvalidMagic(decoded[0])));
+    }
+    if decoded[1] != FORMAT_VERSION {
+        return Err(Box::new(CryptError::UnsupportedVersion(decoded[1])));
+    }
+    let algorithm = Algorithm::from_id(decoded[2])?;
+    let flags = decoded[3];
+    let format = Format::from_id(decoded[4])?;
+    let header = &decoded[..HEADER_LEN];
+    let body = &decoded[HEADER_LEN..];
+
+    let decrypted = if flags & FLAG_HYBRID != 0 {
+        let identity_private_key = match &*MODE.lock().unwrap() {
+            Mode::Identity(identity_private_key) => identity_private_key.clone(),
+            Mode::Symmetric | Mode::Recipient(_) => return Err(Box::new(CryptError::MissingIdentityKey)),
+        };
+        d_hybrid(body, header, &identity_private_key, aad)?
+    } else {
+        d_symmetric(body, header, algorithm, aad)?
+    };
+    format.decode(&decrypted)
+}
+
+/// Concatenates the envelope header, any data specific to this call (e.g. the
+/// key id or ephemeral public key prefixing the body), and the caller's
+/// context into the associated data sealed alongside the ciphertext.
+///
+/// Binding the header in means tampering with any of its bytes — the
+/// algorithm id, flags, or format id — invalidates the AEAD tag instead of
+/// silently changing which algorithm or codec opens the authenticated
+/// plaintext.
+fn bind_aad(header: &[u8], extra: &[u8], caller_aad: &[u8]) -> Vec<u8> {
+    let mut bound = Vec::with_capacity(header.len() + extra.len() + caller_aad.len());
+    bound.extend_from_slice(header);
+    bound.extend_from_slice(extra);
+    bound.extend_from_slice(caller_aad);
+    bound
 }
 
-fn encrypt(mut data: Vec<u8>, nonce: [u8; NONCE_LEN]) -> Result<Vec<u8>, Box<dyn Error>> {
-    let key = MASTER_KEY.lock().unwrap();
-    let (key, nonce) = prepare_key(&key, nonce);
-    let mut encryption_key = SealingKey::new(key, nonce);
+fn e_symmetric(serialized: Vec<u8>, format: Format, aad: &[u8]) -> Result<(Algorithm, Vec<u8>), Box<dyn Error>> {
+    let nonce = generate_random_nonce();
+    let (algorithm, key_id, mut encrypted) = encrypt_primary(serialized, nonce, format, aad)?;
+    let mut body = key_id.to_be_bytes().to_vec();
+    body.extend_from_slice(&nonce);
+    body.append(&mut encrypted);
+    Ok((algorithm, body))
+}
+
+fn d_symmetric(body: &[u8], header: &[u8], algorithm: Algorithm, aad: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    if body.len() < KEY_ID_LEN + NONCE_LEN {
+        return Err(Box::new(CryptError::Truncated));
+    }
+    let key_id = u32::from_be_bytes(body[..KEY_ID_LEN].try_into().unwrap());
+    let nonce: [u8; NONCE_LEN] = body[KEY_ID_LEN..KEY_ID_LEN + NONCE_LEN].try_into().unwrap();
+    let data = body[KEY_ID_LEN + NONCE_LEN..].to_vec();
+    let bound_aad = bind_aad(header, &key_id.to_be_bytes(), aad);
+    decrypt(data, nonce, algorithm, key_id, &bound_aad)
+}
+
+fn e_hybrid(
+    mut data: Vec<u8>,
+    recipient_public_key: [u8; X25519_PUBLIC_KEY_LEN],
+    header: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let nonce = generate_random_nonce();
+    let ephemeral_secret = X25519StaticSecret::from(generate_x25519_scalar());
+    let ephemeral_public_key = X25519PublicKey::from(&ephemeral_secret);
+    let shared_secret = ephemeral_secret.diffie_hellman(&X25519PublicKey::from(recipient_public_key));
+
+    let (key, nonce_sequence) = prepare_key(shared_secret.as_bytes(), Algorithm::Aes256Gcm, nonce);
+    let mut sealing_key = SealingKey::new(key, nonce_sequence);
+    let bound_aad = bind_aad(header, ephemeral_public_key.as_bytes(), aad);
+    sealing_key
+        .seal_in_place_append_tag(Aad::from(bound_aad), &mut data)
+        .map_err(CryptError::EncryptionError)?;
+
+    let mut body = ephemeral_public_key.as_bytes().to_vec();
+    body.extend_from_slice(&nonce);
+    body.append(&mut data);
+    Ok(body)
+}
+
+fn d_hybrid(
+    body: &[u8],
+    header: &[u8],
+    identity_private_key: &[u8; X25519_PUBLIC_KEY_LEN],
+    aad: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    if body.len() < X25519_PUBLIC_KEY_LEN + NONCE_LEN {
+        return Err(Box::new(CryptError::Truncated));
+    }
+    let ephemeral_public_key: [u8; X25519_PUBLIC_KEY_LEN] =
+        body[..X25519_PUBLIC_KEY_LEN].try_into().unwrap();
+    let nonce: [u8; NONCE_LEN] = body[X25519_PUBLIC_KEY_LEN..X25519_PUBLIC_KEY_LEN + NONCE_LEN]
+        .try_into()
+        .unwrap();
+    let mut data = body[X25519_PUBLIC_KEY_LEN + NONCE_LEN..].to_vec();
+
+    let identity_secret = X25519StaticSecret::from(*identity_private_key);
+    let shared_secret = identity_secret.diffie_hellman(&X25519PublicKey::from(ephemeral_public_key));
+
+    let (key, nonce_sequence) = prepare_key(shared_secret.as_bytes(), Algorithm::Aes256Gcm, nonce);
+    let mut opening_key = OpeningKey::new(key, nonce_sequence);
+    let bound_aad = bind_aad(header, &ephemeral_public_key, aad);
+    opening_key
+        .open_in_place(Aad::from(bound_aad), &mut data)
+        .map_err(CryptError::DecryptionError)?;
+    let length = data.len() - Algorithm::Aes256Gcm.ring_algorithm().tag_len();
+
+    let plaintext = data[..length].to_vec();
+    data.zeroize();
+    Ok(plaintext)
+}
+
+/// Picks the primary key and seals `data` under it in one [`MASTER_KEY`] lock
+/// acquisition, so a concurrent [`rotate`]/[`disable_key`] can't interleave
+/// between "pick the primary key" and "seal under it" and have this call seal
+/// a brand-new record under a key that's disabled by the time it runs.
+fn encrypt_primary(
+    mut data: Vec<u8>,
+    nonce: [u8; NONCE_LEN],
+    format: Format,
+    aad: &[u8],
+) -> Result<(Algorithm, u32, Vec<u8>), Box<dyn Error>> {
+    let state = MASTER_KEY.lock().unwrap();
+    let key_id = state.primary_id;
+    let algorithm = state.algorithm;
+    let entry = state.keys.get(&key_id).ok_or(CryptError::UnknownKeyId(key_id))?;
+    if !entry.enabled {
+        return Err(Box::new(CryptError::KeyDisabled(key_id)));
+    }
+
+    let header = [MAGIC, FORMAT_VERSION, algorithm.id(), 0, format.id()];
+    let bound_aad = bind_aad(&header, &key_id.to_be_bytes(), aad);
+    let (key, nonce_sequence) = prepare_key(&entry.key, algorithm, nonce);
+    let mut encryption_key = SealingKey::new(key, nonce_sequence);
     encryption_key
-        .seal_in_place_append_tag(Aad::empty(), &mut data)
+        .seal_in_place_append_tag(Aad::from(bound_aad), &mut data)
         .map_err(CryptError::EncryptionError)?;
 
-    Ok(data)
+    Ok((algorithm, key_id, data))
 }
 
-fn decrypt(mut data: Vec<u8>, nonce: [u8; NONCE_LEN]) -> Result<Vec<u8>, Box<dyn Error>> {
-    let key = MASTER_KEY.lock().unwrap();
-    let (key, nonce) = prepare_key(&key, nonce);
+fn decrypt(
+    mut data: Vec<u8>,
+    nonce: [u8; NONCE_LEN],
+    algorithm: Algorithm,
+    key_id: u32,
+    aad: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let state = MASTER_KEY.lock().unwrap();
+    let entry = state
+        .keys
+        .get(&key_id)
+        .ok_or(CryptError::UnknownKeyId(key_id))?;
+    if !entry.enabled {
+        return Err(Box::new(CryptError::KeyDisabled(key_id)));
+    }
+    let (key, nonce) = prepare_key(&entry.key, algorithm, nonce);
     let mut decryption_key = OpeningKey::new(key, nonce);
     decryption_key
-        .open_in_place(Aad::empty(), &mut data)
+        .open_in_place(Aad::from(aad), &mut data)
         .map_err(CryptError::DecryptionError)?;
-    let length = data.len() - AES_256_GCM.tag_len();
+    let length = data.len() - algorithm.ring_algorithm().tag_len();
 
-    Ok(data[..length].to_vec())
+    let plaintext = data[..length].to_vec();
+    data.zeroize();
+    Ok(plaintext)
 }
 
 #[derive(Debug)]
 pub enum CryptError {
     DecryptionError(Unspecified),
     EncryptionError(Unspecified),
+    UnknownAlgorithm(u8),
+    UnknownFormat(u8),
+    UnknownKeyId(u32),
+    KeyDisabled(u32),
+    CannotDisablePrimaryKey(u32),
+    MissingRecipientKey,
+    MissingIdentityKey,
+    InvalidMagic(u8),
+    UnsupportedVersion(u8),
+    Truncated,
 }
 
 impl Display for CryptError {
@@ -121,6 +620,24 @@ impl Display for CryptError {
         match self {
             Self::DecryptionError(e) => e.fmt(f),
             Self::EncryptionError(e) => e.fmt(f),
+            Self::UnknownAlgorithm(id) => write!(f, "unknown algorithm id: {id}"),
+            Self::UnknownFormat(id) => write!(f, "unknown format id: {id}"),
+            Self::UnknownKeyId(id) => write!(f, "unknown key id: {id}"),
+            Self::KeyDisabled(id) => write!(f, "key {id} has been disabled"),
+            Self::CannotDisablePrimaryKey(id) => {
+                write!(f, "key {id} is the primary key and cannot be disabled; rotate first")
+            }
+            Self::InvalidMagic(byte) => write!(f, "not a serde-crypt envelope (magic byte {byte:#x})"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported envelope format version: {version}")
+            }
+            Self::Truncated => write!(f, "envelope is too short to contain a valid header and body"),
+            Self::MissingRecipientKey => {
+                write!(f, "encryption requires a recipient key set via setup_recipient")
+            }
+            Self::MissingIdentityKey => {
+                write!(f, "decryption requires an identity key set via setup_identity")
+            }
         }
     }
 }
@@ -148,11 +665,26 @@ fn generate_random_nonce() -> [u8; NONCE_LEN] {
     raw_nonce
 }
 
-fn prepare_key(key: &Vec<u8>, nonce: [u8; NONCE_LEN]) -> (UnboundKey, INonceSequence) {
-    let digest = digest(&digest::SHA256, key.as_slice());
-    let key = digest.as_ref();
+fn generate_x25519_scalar() -> [u8; X25519_PUBLIC_KEY_LEN] {
+    let rand_gen = SystemRandom::new();
+    let mut raw_scalar = [0u8; X25519_PUBLIC_KEY_LEN];
+    rand_gen.fill(&mut raw_scalar).unwrap();
+    raw_scalar
+}
+
+fn prepare_key(key: &[u8], algorithm: Algorithm, nonce: [u8; NONCE_LEN]) -> (UnboundKey, INonceSequence) {
+    let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, &[]).extract(key);
+    let okm = prk
+        .expand(&[b"serde_crypt"], OkmLength(algorithm.key_len()))
+        .unwrap();
+    let mut derived = Zeroizing::new(vec![0u8; algorithm.key_len()]);
+    okm.fill(&mut derived).unwrap();
+
     let nonce_sequence = INonceSequence::new(Nonce::assume_unique_for_key(nonce));
-    (UnboundKey::new(&AES_256_GCM, key).unwrap(), nonce_sequence)
+    (
+        UnboundKey::new(algorithm.ring_algorithm(), &derived).unwrap(),
+        nonce_sequence,
+    )
 }
 
 #[cfg(test)]
@@ -160,7 +692,19 @@ mod test {
     use ring::rand::{SecureRandom, SystemRandom};
     use serde::{Deserialize, Serialize};
 
-    use crate::setup;
+    use std::sync::Mutex;
+
+    use base64::Engine;
+
+    use crate::{
+        disable_key, e, rotate, set_context, set_format, setup, setup_identity, setup_recipient, setup_with,
+        teardown, Algorithm, Format,
+    };
+
+    // `setup`/`rotate`/`disable_key`/`setup_recipient` all mutate process-global
+    // state, so tests that exercise them must not run concurrently with each
+    // other.
+    static TEST_LOCK: Mutex<()> = Mutex::new(());
 
     #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
     struct Other {
@@ -180,6 +724,7 @@ mod test {
 
     #[test]
     fn flow() -> Result<(), serde_json::Error> {
+        let _guard = TEST_LOCK.lock().unwrap();
         let mut key: [u8; 256] = [0; 256];
         let rand_gen = SystemRandom::new();
         rand_gen.fill(&mut key).unwrap();
@@ -210,6 +755,7 @@ mod test {
 
     #[test]
     fn readme() -> Result<(), serde_json::Error> {
+        let _guard = TEST_LOCK.lock().unwrap();
         let mut key: [u8; 256] = [0; 256];
         let rand_gen = SystemRandom::new();
         rand_gen.fill(&mut key).unwrap();
@@ -226,4 +772,223 @@ mod test {
         assert_eq!(deserialized, data);
         Ok(())
     }
+
+    #[test]
+    fn flow_with_chacha20_poly1305() -> Result<(), serde_json::Error> {
+        let _guard = TEST_LOCK.lock().unwrap();
+        let mut key: [u8; 256] = [0; 256];
+        let rand_gen = SystemRandom::new();
+        rand_gen.fill(&mut key).unwrap();
+
+        setup_with(key.to_vec(), Algorithm::ChaCha20Poly1305);
+        let data = Example {
+            private: "private data".to_string(),
+            public: "public data".to_string(),
+        };
+
+        let serialized = serde_json::to_string(&data)?;
+        let deserialized: Example = serde_json::from_str(&serialized)?;
+
+        assert_eq!(deserialized, data);
+        Ok(())
+    }
+
+    #[test]
+    fn rotate_keeps_old_records_readable() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        let mut key: [u8; 256] = [0; 256];
+        let rand_gen = SystemRandom::new();
+        rand_gen.fill(&mut key).unwrap();
+        setup(key.to_vec());
+
+        let old_encrypted = e("a secret message".to_string()).unwrap();
+
+        let mut new_key: [u8; 256] = [1; 256];
+        rand_gen.fill(&mut new_key).unwrap();
+        rotate(new_key.to_vec());
+
+        let new_encrypted = e("another secret message".to_string()).unwrap();
+
+        let decrypted: String = crate::d(old_encrypted).unwrap();
+        assert_eq!(decrypted, "a secret message");
+        let decrypted: String = crate::d(new_encrypted).unwrap();
+        assert_eq!(decrypted, "another secret message");
+    }
+
+    #[test]
+    fn disable_key_stops_decryption() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        let mut key: [u8; 256] = [0; 256];
+        let rand_gen = SystemRandom::new();
+        rand_gen.fill(&mut key).unwrap();
+        setup(key.to_vec());
+
+        let encrypted = e("a secret message".to_string()).unwrap();
+
+        let mut new_key: [u8; 256] = [1; 256];
+        rand_gen.fill(&mut new_key).unwrap();
+        rotate(new_key.to_vec());
+        disable_key(0).unwrap();
+
+        let result: Result<String, _> = crate::d(encrypted);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn disable_key_rejects_the_primary_key() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        let mut key: [u8; 256] = [0; 256];
+        let rand_gen = SystemRandom::new();
+        rand_gen.fill(&mut key).unwrap();
+        setup(key.to_vec());
+
+        let result = disable_key(0);
+        assert!(result.is_err());
+
+        // The primary key is still enabled: previously-sealed data (and new
+        // data) both keep decrypting.
+        let encrypted = e("a secret message".to_string()).unwrap();
+        let decrypted: String = crate::d(encrypted).unwrap();
+        assert_eq!(decrypted, "a secret message");
+    }
+
+    #[test]
+    fn teardown_clears_the_keyring() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        let mut key: [u8; 256] = [0; 256];
+        let rand_gen = SystemRandom::new();
+        rand_gen.fill(&mut key).unwrap();
+        setup(key.to_vec());
+
+        let encrypted = e("a secret message".to_string()).unwrap();
+        teardown();
+
+        let result: Result<String, _> = crate::d(encrypted);
+        assert!(result.is_err());
+
+        // A fresh `setup` after `teardown` works as if nothing had run before.
+        rand_gen.fill(&mut key).unwrap();
+        setup(key.to_vec());
+        let encrypted = e("another secret message".to_string()).unwrap();
+        let decrypted: String = crate::d(encrypted).unwrap();
+        assert_eq!(decrypted, "another secret message");
+    }
+
+    #[test]
+    fn flow_with_bincode_format() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        let mut key: [u8; 256] = [0; 256];
+        let rand_gen = SystemRandom::new();
+        rand_gen.fill(&mut key).unwrap();
+        setup(key.to_vec());
+
+        set_format(Format::Bincode);
+        let instance = Test {
+            field: "a secret message".as_bytes().to_vec(),
+            other: Other {
+                field: "another secret message".as_bytes().to_vec(),
+                plain: "this is a plain nested string".to_string(),
+            },
+            plain: "this is a plain string".to_string(),
+        };
+        let encrypted = e(&instance).unwrap();
+
+        // A later change to the current format doesn't affect an envelope
+        // that already recorded which format it was encoded with.
+        set_format(Format::Json);
+        let decrypted: Test = crate::d(encrypted).unwrap();
+        assert_eq!(decrypted, instance);
+    }
+
+    #[test]
+    fn hybrid_flow() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        use x25519_dalek::{PublicKey, StaticSecret};
+
+        let rand_gen = SystemRandom::new();
+        let mut identity_bytes = [0u8; 32];
+        rand_gen.fill(&mut identity_bytes).unwrap();
+        let identity_secret = StaticSecret::from(identity_bytes);
+        let recipient_public_key = PublicKey::from(&identity_secret);
+
+        setup_recipient(*recipient_public_key.as_bytes());
+        let encrypted = e("a secret message".to_string()).unwrap();
+
+        setup_identity(identity_bytes);
+        let decrypted: String = crate::d(encrypted).unwrap();
+
+        assert_eq!(decrypted, "a secret message");
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct WithAad {
+        #[serde(with = "crate::aad")]
+        field: String,
+    }
+
+    #[test]
+    fn aad_binds_ciphertext_to_context() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        let mut key: [u8; 256] = [0; 256];
+        let rand_gen = SystemRandom::new();
+        rand_gen.fill(&mut key).unwrap();
+        setup(key.to_vec());
+
+        set_context(b"record-1".to_vec());
+        let instance = WithAad {
+            field: "a secret message".to_string(),
+        };
+        let serialized = serde_json::to_string(&instance).unwrap();
+
+        let deserialized: WithAad = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, instance);
+
+        set_context(b"record-2".to_vec());
+        let result: Result<WithAad, _> = serde_json::from_str(&serialized);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn truncated_envelope_errors_instead_of_panicking() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        let mut key: [u8; 256] = [0; 256];
+        let rand_gen = SystemRandom::new();
+        rand_gen.fill(&mut key).unwrap();
+        setup(key.to_vec());
+
+        let encrypted = e("a secret message".to_string()).unwrap();
+        let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(encrypted.as_bytes())
+            .unwrap();
+
+        let garbage = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode([0u8; 2]);
+        let result: Result<String, _> = crate::d(garbage);
+        assert!(result.is_err());
+
+        let wrong_magic = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&decoded[1..]);
+        let result: Result<String, _> = crate::d(wrong_magic);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn tampering_with_the_header_invalidates_the_tag() {
+        let _guard = TEST_LOCK.lock().unwrap();
+        let mut key: [u8; 256] = [0; 256];
+        let rand_gen = SystemRandom::new();
+        rand_gen.fill(&mut key).unwrap();
+        setup_with(key.to_vec(), Algorithm::Aes256Gcm);
+
+        let encrypted = e("a secret message".to_string()).unwrap();
+        let mut decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(encrypted.as_bytes())
+            .unwrap();
+
+        // Flip the format id byte (JSON -> Bincode): the header is bound into
+        // the AAD, so this must invalidate the tag rather than silently
+        // decrypting the authenticated plaintext with the wrong codec.
+        decoded[4] ^= 0x01;
+        let tampered = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(decoded);
+        let result: Result<String, _> = crate::d(tampered);
+        assert!(result.is_err());
+    }
 }
@@ -1,21 +1,204 @@
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields};
 
+/// Generates a `Sealed<Name>` companion type that holds the whole struct
+/// sealed behind a single base64 field, so callers can encrypt a struct with
+/// one derive instead of annotating every field with
+/// `#[serde(with = "serde_crypt")]`.
+///
+/// Fields marked `#[serde_crypt(skip)]` are left out of the sealed blob
+/// entirely and kept in the clear as ordinary fields on the companion type
+/// instead, e.g. for a record id a caller needs to read without decrypting
+/// the rest.
+///
+/// Requires every field type of `Name` to implement `Serialize`/`Deserialize`,
+/// and the crate invoking the macro to depend on both `serde` and
+/// `serde_crypt`.
+///
+/// ```ignore
+/// #[derive(Serialize, Deserialize, GenSealed)]
+/// struct Account {
+///     #[serde_crypt(skip)]
+///     id: u64,
+///     balance_cents: i64,
+/// }
+///
+/// let sealed = SealedAccount::try_from(account)?;
+/// let account = Account::try_from(sealed)?;
+/// ```
 #[proc_macro_derive(GenSealed, attributes(serde_crypt))]
 pub fn serde_crypt(input: TokenStream) -> TokenStream {
-    let ast = parse_macro_input!(input as DeriveInput);
+    let ast = syn::parse_macro_input!(input as DeriveInput);
+    expand(ast).into()
+}
+
+fn expand(ast: DeriveInput) -> TokenStream2 {
     let vis = ast.vis;
     let name = ast.ident;
+    let sealed_name = format_ident!("Sealed{}", name);
+    let payload_name = format_ident!("__{}SealedPayload", name);
+
     let fields = match &ast.data {
-        syn::Data::Struct(ref data_struct) => &data_struct.fields,
+        Data::Struct(data_struct) => match &data_struct.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => panic!("#[derive(GenSealed)] may only be used on structs with named fields"),
+        },
         _ => panic!("#[derive(GenSealed)] may only be used on structs"),
     };
 
-    let sealed_type = quote! {
-        #vis struct #name {
-            #fields
+    let (plain_fields, sealed_fields): (Vec<_>, Vec<_>) =
+        fields.iter().partition(|field| is_skipped(field));
+
+    let plain_idents: Vec<_> = plain_fields.iter().map(|field| field.ident.as_ref().unwrap()).collect();
+    let plain_types: Vec<_> = plain_fields.iter().map(|field| &field.ty).collect();
+    let sealed_idents: Vec<_> = sealed_fields.iter().map(|field| field.ident.as_ref().unwrap()).collect();
+    let sealed_types: Vec<_> = sealed_fields.iter().map(|field| &field.ty).collect();
+
+    quote! {
+        // Holds only the fields that actually get encrypted, so a
+        // `#[serde_crypt(skip)]` field never ends up serialized into the
+        // sealed blob even though it's also kept in the clear below.
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct #payload_name {
+            #( #sealed_idents: #sealed_types, )*
         }
-    };
-    sealed_type.into()
+
+        #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+        #vis struct #sealed_name {
+            #vis sealed: String,
+            #( #vis #plain_idents: #plain_types, )*
+        }
+
+        impl std::convert::TryFrom<#name> for #sealed_name {
+            type Error = Box<dyn std::error::Error>;
+
+            fn try_from(value: #name) -> Result<Self, Self::Error> {
+                let payload = #payload_name {
+                    #( #sealed_idents: value.#sealed_idents, )*
+                };
+                let sealed = serde_crypt::e(payload)?;
+                #( let #plain_idents = value.#plain_idents; )*
+                Ok(#sealed_name {
+                    sealed,
+                    #( #plain_idents, )*
+                })
+            }
+        }
+
+        impl std::convert::TryFrom<#sealed_name> for #name {
+            type Error = Box<dyn std::error::Error>;
+
+            fn try_from(value: #sealed_name) -> Result<Self, Self::Error> {
+                let payload: #payload_name = serde_crypt::d(value.sealed)?;
+                Ok(#name {
+                    #( #sealed_idents: payload.#sealed_idents, )*
+                    #( #plain_idents: value.#plain_idents, )*
+                })
+            }
+        }
+    }
+}
+
+fn is_skipped(field: &syn::Field) -> bool {
+    field.attrs.iter().any(|attr| {
+        if !attr.path().is_ident("serde_crypt") {
+            return false;
+        }
+        let mut skip = false;
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+            }
+            Ok(())
+        });
+        skip
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use syn::{parse_quote, Item};
+
+    fn struct_fields(file: &syn::File, name: &str) -> Vec<String> {
+        file.items
+            .iter()
+            .find_map(|item| match item {
+                Item::Struct(item_struct) if item_struct.ident == name => Some(
+                    item_struct
+                        .fields
+                        .iter()
+                        .map(|field| field.ident.as_ref().unwrap().to_string())
+                        .collect(),
+                ),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("generated code has no struct named {name}"))
+    }
+
+    #[test]
+    fn skip_fields_are_kept_in_the_clear_and_out_of_the_payload() {
+        let ast: DeriveInput = parse_quote! {
+            struct Account {
+                #[serde_crypt(skip)]
+                id: u64,
+                balance_cents: i64,
+            }
+        };
+
+        let generated = expand(ast);
+        let file: syn::File = syn::parse2(generated).expect("generated code must parse");
+
+        let sealed_fields = struct_fields(&file, "SealedAccount");
+        assert!(sealed_fields.contains(&"sealed".to_string()));
+        assert!(sealed_fields.contains(&"id".to_string()));
+        assert!(!sealed_fields.contains(&"balance_cents".to_string()));
+
+        let payload_fields = struct_fields(&file, "__AccountSealedPayload");
+        assert!(payload_fields.contains(&"balance_cents".to_string()));
+        assert!(!payload_fields.contains(&"id".to_string()));
+    }
+
+    #[test]
+    fn no_skipped_fields_leaves_the_payload_holding_everything() {
+        let ast: DeriveInput = parse_quote! {
+            struct Example {
+                private: String,
+                public: String,
+            }
+        };
+
+        let generated = expand(ast);
+        let file: syn::File = syn::parse2(generated).expect("generated code must parse");
+
+        let sealed_fields = struct_fields(&file, "SealedExample");
+        assert_eq!(sealed_fields, vec!["sealed".to_string()]);
+
+        let payload_fields = struct_fields(&file, "__ExampleSealedPayload");
+        assert!(payload_fields.contains(&"private".to_string()));
+        assert!(payload_fields.contains(&"public".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "named fields")]
+    fn tuple_structs_are_rejected() {
+        let ast: DeriveInput = parse_quote! {
+            struct Account(u64, i64);
+        };
+        expand(ast);
+    }
+
+    #[test]
+    #[should_panic(expected = "may only be used on structs")]
+    fn enums_are_rejected() {
+        let ast: DeriveInput = parse_quote! {
+            enum Account {
+                Checking,
+                Savings,
+            }
+        };
+        expand(ast);
+    }
 }